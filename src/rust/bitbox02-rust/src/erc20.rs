@@ -0,0 +1,133 @@
+// Copyright 2024 Shift Crypto AG
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Resolves ERC20 token metadata (unit, decimals) for amount formatting, falling back to
+//! host-supplied metadata for contracts the on-device table doesn't know about.
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+
+use bitbox02::app_eth::erc20_params_get;
+
+/// ERC20 token metadata supplied directly by the host for a `(chain_id, contract_address)` that
+/// is not in the on-device table. Must be validated with [`validate`] before use.
+pub struct HostParams {
+    pub unit: String,
+    pub decimals: u8,
+}
+
+/// `unit` is bounded to comfortably fit real-world ticker symbols (the longest ERC20 symbols seen
+/// in practice, e.g. wrapped/LP tokens, run to single digits; this leaves headroom without
+/// accepting an unbounded string onto a fixed-width confirmation screen). `decimals` is bounded to
+/// 36, well below the 255 that a `uint8` ERC20 `decimals()` return value could hold.
+const MAX_HOST_UNIT_LEN: usize = 11;
+const MAX_HOST_DECIMALS: u8 = 36;
+
+fn validate(params: &HostParams) -> Result<(), ()> {
+    if params.decimals > MAX_HOST_DECIMALS {
+        return Err(());
+    }
+    if params.unit.is_empty() || params.unit.len() > MAX_HOST_UNIT_LEN || !params.unit.is_ascii() {
+        return Err(());
+    }
+    Ok(())
+}
+
+/// ERC20 params resolved for amount formatting: either from the trusted on-device table, or from
+/// host-supplied metadata for a contract the table doesn't know about.
+pub struct ResolvedParams {
+    pub unit: Cow<'static, str>,
+    pub contract_address: [u8; 20],
+    pub decimals: u8,
+    /// Set when `unit`/`decimals` came from the host rather than the on-device table. Callers
+    /// must show an "unverified token" warning with the full contract address before signing.
+    pub unverified: bool,
+}
+
+/// Resolves ERC20 params for `(chain_id, contract_address)`, trying the on-device table first and
+/// only falling back to `host_params` — validated here — when the table lookup misses.
+///
+/// Not yet called from the ERC20 transfer confirmation flow; swapping its direct
+/// `erc20_params_get` call over to this function (and rendering the `unverified` warning screen)
+/// is tracked as follow-up work outside this module.
+pub fn resolve(
+    chain_id: u64,
+    contract_address: [u8; 20],
+    host_params: Option<&HostParams>,
+) -> Result<Option<ResolvedParams>, ()> {
+    if let Some(params) = erc20_params_get(chain_id, contract_address) {
+        return Ok(Some(ResolvedParams {
+            unit: Cow::Borrowed(params.unit),
+            contract_address: params.contract_address,
+            decimals: params.decimals,
+            unverified: false,
+        }));
+    }
+    let host_params = match host_params {
+        Some(host_params) => host_params,
+        None => return Ok(None),
+    };
+    validate(host_params)?;
+    Ok(Some(ResolvedParams {
+        unit: Cow::Owned(host_params.unit.clone()),
+        contract_address,
+        decimals: host_params.decimals,
+        unverified: true,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_resolve_no_fallback() {
+        // Unknown to the on-device table, no host params supplied: no params to format with.
+        assert!(resolve(0, [0xff; 20], None).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_resolve_host_fallback() {
+        let host_params = HostParams {
+            unit: "FOO".to_string(),
+            decimals: 18,
+        };
+        let resolved = resolve(0, [0xff; 20], Some(&host_params)).unwrap().unwrap();
+        assert_eq!(resolved.unit, "FOO");
+        assert_eq!(resolved.decimals, 18);
+        assert!(resolved.unverified);
+    }
+
+    #[test]
+    fn test_resolve_rejects_bad_host_params() {
+        let too_many_decimals = HostParams {
+            unit: "FOO".to_string(),
+            decimals: 37,
+        };
+        assert!(resolve(0, [0xff; 20], Some(&too_many_decimals)).is_err());
+
+        let non_ascii_unit = HostParams {
+            unit: "Ƒ".to_string(),
+            decimals: 18,
+        };
+        assert!(resolve(0, [0xff; 20], Some(&non_ascii_unit)).is_err());
+
+        let empty_unit = HostParams {
+            unit: "".to_string(),
+            decimals: 18,
+        };
+        assert!(resolve(0, [0xff; 20], Some(&empty_unit)).is_err());
+    }
+}
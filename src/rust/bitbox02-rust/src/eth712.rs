@@ -0,0 +1,576 @@
+// Copyright 2024 Shift Crypto AG
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! On-device EIP-712 typed-structured-data hashing, so permits, DEX orders and similar messages
+//! no longer have to be blind-signed as an opaque digest.
+//! See https://eips.ethereum.org/EIPS/eip-712.
+
+use alloc::collections::BTreeSet;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use sha3::{Digest, Keccak256};
+
+/// Recursion limit for walking a `Value` tree (`Array`/`Struct` nesting). The host is untrusted,
+/// so a deeply-nested or self-referential-looking payload must be rejected rather than recurse
+/// until the device runs out of stack.
+const MAX_DEPTH: u32 = 32;
+
+/// A single member of an EIP-712 struct type, e.g. `{ name: "wallet", type_name: "address" }` in
+/// `Person(string name,address wallet)`.
+pub struct FieldType {
+    pub name: String,
+    pub type_name: String,
+}
+
+/// All struct type definitions referenced by a typed-data payload, keyed by type name. Must
+/// contain an `"EIP712Domain"` entry.
+pub type TypeSet = alloc::collections::BTreeMap<String, Vec<FieldType>>;
+
+/// An atomic (non-dynamic, non-struct) EIP-712 value, already reduced to the bytes that go into
+/// its ABI word. `Number` is the big-endian two's-complement representation of a `uintN`/`intN`
+/// value, which may be shorter than 32 bytes: `encode_atomic` zero-pads it for `uintN`, and
+/// sign-extends it (based on its high bit) for `intN`. `FixedBytes` is the raw contents of a
+/// `bytesN` value, right-padded with zero bytes to 32.
+pub enum AtomicValue {
+    Number(Vec<u8>),
+    Bool(bool),
+    Address([u8; 20]),
+    FixedBytes(Vec<u8>),
+}
+
+/// A concrete value bound to a struct member or to the top-level domain/message.
+pub enum Value {
+    Atomic(AtomicValue),
+    String(String),
+    Bytes(Vec<u8>),
+    Array(Vec<Value>),
+    Struct(Vec<(String, Value)>),
+}
+
+fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(data);
+    let mut out = [0u8; 32];
+    out.copy_from_slice(&hasher.finalize());
+    out
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(2 + bytes.len() * 2);
+    s.push_str("0x");
+    for b in bytes {
+        s.push_str(&format!("{:02x}", b));
+    }
+    s
+}
+
+/// Strips any `[]`/`[N]` array suffix, returning the element type name.
+fn base_type_name(type_name: &str) -> &str {
+    match type_name.find('[') {
+        Some(idx) => &type_name[..idx],
+        None => type_name,
+    }
+}
+
+fn type_string(type_name: &str, types: &TypeSet) -> Result<String, ()> {
+    let fields = types.get(type_name).ok_or(())?;
+    let members: Vec<String> = fields
+        .iter()
+        .map(|field| format!("{} {}", field.type_name, field.name))
+        .collect();
+    Ok(format!("{}({})", type_name, members.join(",")))
+}
+
+fn collect_dependencies(type_name: &str, types: &TypeSet, deps: &mut BTreeSet<String>) {
+    let base = base_type_name(type_name);
+    if deps.contains(base) {
+        return;
+    }
+    let fields = match types.get(base) {
+        Some(fields) => fields,
+        // Not a struct type: an atomic type like `address`, or unknown.
+        None => return,
+    };
+    deps.insert(base.into());
+    for field in fields {
+        collect_dependencies(&field.type_name, types, deps);
+    }
+}
+
+/// `encodeType(primaryType)`: the primary type's own definition, followed by the definitions of
+/// all struct types it references (directly or transitively), sorted alphabetically by name.
+pub fn encode_type(primary_type: &str, types: &TypeSet) -> Result<String, ()> {
+    let mut deps = BTreeSet::new();
+    collect_dependencies(primary_type, types, &mut deps);
+    deps.remove(primary_type);
+
+    let mut result = type_string(primary_type, types)?;
+    for dep in deps {
+        result.push_str(&type_string(&dep, types)?);
+    }
+    Ok(result)
+}
+
+fn type_hash(primary_type: &str, types: &TypeSet) -> Result<[u8; 32], ()> {
+    Ok(keccak256(encode_type(primary_type, types)?.as_bytes()))
+}
+
+fn encode_atomic(type_name: &str, value: &AtomicValue) -> Result<[u8; 32], ()> {
+    let mut word = [0u8; 32];
+    match (type_name, value) {
+        ("bool", AtomicValue::Bool(b)) => word[31] = *b as u8,
+        ("address", AtomicValue::Address(addr)) => word[12..].copy_from_slice(addr),
+        (t, AtomicValue::Number(n)) if t.starts_with("uint") => {
+            if n.len() > 32 {
+                return Err(());
+            }
+            word[32 - n.len()..].copy_from_slice(n);
+        }
+        (t, AtomicValue::Number(n)) if t.starts_with("int") => {
+            if n.is_empty() || n.len() > 32 {
+                return Err(());
+            }
+            // Sign-extend into the high bytes so a short two's-complement value (e.g. `int8(-1)`
+            // as `[0xff]`) keeps its value instead of turning into a large positive number.
+            let pad = if n[0] & 0x80 != 0 { 0xff } else { 0 };
+            for b in word[..32 - n.len()].iter_mut() {
+                *b = pad;
+            }
+            word[32 - n.len()..].copy_from_slice(n);
+        }
+        (t, AtomicValue::FixedBytes(b)) if t.starts_with("bytes") => {
+            if b.len() > 32 {
+                return Err(());
+            }
+            word[..b.len()].copy_from_slice(b);
+        }
+        _ => return Err(()),
+    }
+    Ok(word)
+}
+
+/// `encodeData`'s per-member 32-byte word: atomic values padded per ABI, dynamic `string`/`bytes`
+/// as their own hash, nested structs as `hashStruct`, arrays as the hash of their concatenated
+/// encoded elements.
+fn encode_value(
+    type_name: &str,
+    value: &Value,
+    types: &TypeSet,
+    depth: u32,
+) -> Result<[u8; 32], ()> {
+    if depth > MAX_DEPTH {
+        return Err(());
+    }
+    if let Some(element_type_end) = type_name.rfind('[') {
+        let elements = match value {
+            Value::Array(elements) => elements,
+            _ => return Err(()),
+        };
+        let element_type = &type_name[..element_type_end];
+        let mut concatenated = Vec::with_capacity(elements.len() * 32);
+        for element in elements {
+            concatenated.extend_from_slice(&encode_value(element_type, element, types, depth + 1)?);
+        }
+        return Ok(keccak256(&concatenated));
+    }
+
+    if types.contains_key(type_name) {
+        return hash_struct_impl(type_name, value, types, depth + 1);
+    }
+
+    match value {
+        Value::Atomic(atomic) => encode_atomic(type_name, atomic),
+        Value::String(s) => Ok(keccak256(s.as_bytes())),
+        Value::Bytes(b) => Ok(keccak256(b)),
+        _ => Err(()),
+    }
+}
+
+/// `hashStruct(s) = keccak256(typeHash ‖ encodeData(s))`.
+pub fn hash_struct(type_name: &str, value: &Value, types: &TypeSet) -> Result<[u8; 32], ()> {
+    hash_struct_impl(type_name, value, types, 0)
+}
+
+fn hash_struct_impl(
+    type_name: &str,
+    value: &Value,
+    types: &TypeSet,
+    depth: u32,
+) -> Result<[u8; 32], ()> {
+    if depth > MAX_DEPTH {
+        return Err(());
+    }
+    let fields = match value {
+        Value::Struct(fields) => fields,
+        _ => return Err(()),
+    };
+    let field_types = types.get(type_name).ok_or(())?;
+
+    let mut encoded = Vec::with_capacity(32 + field_types.len() * 32);
+    encoded.extend_from_slice(&type_hash(type_name, types)?);
+    for field_type in field_types {
+        let (_, field_value) = fields
+            .iter()
+            .find(|(name, _)| name == &field_type.name)
+            .ok_or(())?;
+        encoded.extend_from_slice(&encode_value(
+            &field_type.type_name,
+            field_value,
+            types,
+            depth + 1,
+        )?);
+    }
+    Ok(keccak256(&encoded))
+}
+
+/// A fully-specified EIP-712 typed-data payload, as the host supplies it.
+pub struct TypedData {
+    pub types: TypeSet,
+    pub primary_type: String,
+    pub domain: Value,
+    pub message: Value,
+}
+
+/// Computes `keccak256(0x1901 ‖ hashStruct(EIP712Domain) ‖ hashStruct(message))`, the digest that
+/// gets ecdsa-signed.
+///
+/// Nothing in this crate calls this function or [`message_display_fields`]; the Ethereum signing
+/// request handler does not use them.
+pub fn hash(typed_data: &TypedData) -> Result<[u8; 32], ()> {
+    let domain_hash = hash_struct("EIP712Domain", &typed_data.domain, &typed_data.types)?;
+    let message_hash = hash_struct(
+        &typed_data.primary_type,
+        &typed_data.message,
+        &typed_data.types,
+    )?;
+
+    let mut preimage = Vec::with_capacity(2 + 32 + 32);
+    preimage.extend_from_slice(b"\x19\x01");
+    preimage.extend_from_slice(&domain_hash);
+    preimage.extend_from_slice(&message_hash);
+    Ok(keccak256(&preimage))
+}
+
+/// Pulls the `name` and `chainId` domain fields out for display, so the confirmation screen can
+/// show what is being signed instead of a raw digest. Either may be absent: not every typed-data
+/// payload sets both.
+pub fn domain_display_fields(domain: &Value) -> (Option<String>, Option<Vec<u8>>) {
+    let fields = match domain {
+        Value::Struct(fields) => fields,
+        _ => return (None, None),
+    };
+    let name = fields
+        .iter()
+        .find(|(name, _)| name == "name")
+        .and_then(|(_, value)| match value {
+            Value::String(s) => Some(s.clone()),
+            _ => None,
+        });
+    let chain_id = fields
+        .iter()
+        .find(|(name, _)| name == "chainId")
+        .and_then(|(_, value)| match value {
+            Value::Atomic(AtomicValue::Number(n)) => Some(n.clone()),
+            _ => None,
+        });
+    (name, chain_id)
+}
+
+/// A `message` field value reduced to something a confirmation screen can render: leaves become
+/// text (numbers and fixed/dynamic bytes as hex, strings and bools as-is), nested structs and
+/// arrays keep their shape so the screen can walk them field by field.
+pub enum DisplayValue {
+    Text(String),
+    Struct(Vec<(String, DisplayValue)>),
+    Array(Vec<DisplayValue>),
+}
+
+fn display_atomic(value: &AtomicValue) -> String {
+    match value {
+        AtomicValue::Bool(b) => String::from(if *b { "true" } else { "false" }),
+        AtomicValue::Address(addr) => to_hex(addr),
+        AtomicValue::Number(n) => to_hex(n),
+        AtomicValue::FixedBytes(b) => to_hex(b),
+    }
+}
+
+fn display_value(
+    type_name: &str,
+    value: &Value,
+    types: &TypeSet,
+    depth: u32,
+) -> Result<DisplayValue, ()> {
+    if depth > MAX_DEPTH {
+        return Err(());
+    }
+    if let Some(element_type_end) = type_name.rfind('[') {
+        let elements = match value {
+            Value::Array(elements) => elements,
+            _ => return Err(()),
+        };
+        let element_type = &type_name[..element_type_end];
+        let mut out = Vec::with_capacity(elements.len());
+        for element in elements {
+            out.push(display_value(element_type, element, types, depth + 1)?);
+        }
+        return Ok(DisplayValue::Array(out));
+    }
+
+    if let Some(field_types) = types.get(type_name) {
+        let fields = match value {
+            Value::Struct(fields) => fields,
+            _ => return Err(()),
+        };
+        let mut out = Vec::with_capacity(field_types.len());
+        for field_type in field_types {
+            let (_, field_value) = fields
+                .iter()
+                .find(|(name, _)| name == &field_type.name)
+                .ok_or(())?;
+            out.push((
+                field_type.name.clone(),
+                display_value(&field_type.type_name, field_value, types, depth + 1)?,
+            ));
+        }
+        return Ok(DisplayValue::Struct(out));
+    }
+
+    Ok(DisplayValue::Text(match value {
+        Value::Atomic(atomic) => display_atomic(atomic),
+        Value::String(s) => s.clone(),
+        Value::Bytes(b) => to_hex(b),
+        _ => return Err(()),
+    }))
+}
+
+/// Walks `typed_data.message` into [`DisplayValue`]s keyed by field name, so the confirmation
+/// screen can show what is actually being signed (recipient, amount, order terms, ...) instead of
+/// just the domain.
+pub fn message_display_fields(typed_data: &TypedData) -> Result<Vec<(String, DisplayValue)>, ()> {
+    match display_value(
+        &typed_data.primary_type,
+        &typed_data.message,
+        &typed_data.types,
+        0,
+    )? {
+        DisplayValue::Struct(fields) => Ok(fields),
+        _ => Err(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    // The canonical "Mail" example from https://eips.ethereum.org/EIPS/eip-712.
+    fn mail_types() -> TypeSet {
+        let mut types = TypeSet::new();
+        types.insert(
+            "EIP712Domain".into(),
+            vec![
+                FieldType {
+                    name: "name".into(),
+                    type_name: "string".into(),
+                },
+                FieldType {
+                    name: "version".into(),
+                    type_name: "string".into(),
+                },
+                FieldType {
+                    name: "chainId".into(),
+                    type_name: "uint256".into(),
+                },
+                FieldType {
+                    name: "verifyingContract".into(),
+                    type_name: "address".into(),
+                },
+            ],
+        );
+        types.insert(
+            "Person".into(),
+            vec![
+                FieldType {
+                    name: "name".into(),
+                    type_name: "string".into(),
+                },
+                FieldType {
+                    name: "wallet".into(),
+                    type_name: "address".into(),
+                },
+            ],
+        );
+        types.insert(
+            "Mail".into(),
+            vec![
+                FieldType {
+                    name: "from".into(),
+                    type_name: "Person".into(),
+                },
+                FieldType {
+                    name: "to".into(),
+                    type_name: "Person".into(),
+                },
+                FieldType {
+                    name: "contents".into(),
+                    type_name: "string".into(),
+                },
+            ],
+        );
+        types
+    }
+
+    #[test]
+    fn test_encode_type() {
+        let types = mail_types();
+        assert_eq!(
+            encode_type("Mail", &types).unwrap(),
+            "Mail(Person from,Person to,string contents)Person(string name,address wallet)"
+        );
+        assert_eq!(
+            encode_type("EIP712Domain", &types).unwrap(),
+            "EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)"
+        );
+    }
+
+    #[test]
+    fn test_encode_atomic_negative_int() {
+        // int8(-1), given as its single-byte two's-complement form, must sign-extend to the
+        // 32-byte two's-complement form of -1 (all 0xff), not zero-extend to 255.
+        let word = encode_atomic("int8", &AtomicValue::Number(vec![0xff])).unwrap();
+        assert_eq!(word, [0xffu8; 32]);
+
+        // A positive intN must still zero-extend.
+        let word = encode_atomic("int16", &AtomicValue::Number(vec![0x01, 0x00])).unwrap();
+        let mut want = [0u8; 32];
+        want[30] = 0x01;
+        assert_eq!(word, want);
+
+        // uintN always zero-extends, even with a high bit set.
+        let word = encode_atomic("uint8", &AtomicValue::Number(vec![0xff])).unwrap();
+        let mut want = [0u8; 32];
+        want[31] = 0xff;
+        assert_eq!(word, want);
+    }
+
+    fn mail_domain() -> Value {
+        Value::Struct(vec![
+            ("name".into(), Value::String("Ether Mail".into())),
+            ("version".into(), Value::String("1".into())),
+            (
+                "chainId".into(),
+                Value::Atomic(AtomicValue::Number(vec![1])),
+            ),
+            (
+                "verifyingContract".into(),
+                Value::Atomic(AtomicValue::Address([0xcc; 20])),
+            ),
+        ])
+    }
+
+    fn mail_person(name: &str, wallet: [u8; 20]) -> Value {
+        Value::Struct(vec![
+            ("name".into(), Value::String(name.into())),
+            ("wallet".into(), Value::Atomic(AtomicValue::Address(wallet))),
+        ])
+    }
+
+    fn mail(contents: &str) -> TypedData {
+        TypedData {
+            types: mail_types(),
+            primary_type: "Mail".into(),
+            domain: mail_domain(),
+            message: Value::Struct(vec![
+                ("from".into(), mail_person("Cow", [0xaa; 20])),
+                ("to".into(), mail_person("Bob", [0xbb; 20])),
+                ("contents".into(), Value::String(contents.into())),
+            ]),
+        }
+    }
+
+    #[test]
+    fn test_hash_differs_per_message() {
+        let digest_a = hash(&mail("Hello, Bob!")).unwrap();
+        let digest_b = hash(&mail("Hello, Alice!")).unwrap();
+        assert_ne!(digest_a, digest_b);
+        assert_eq!(digest_a, hash(&mail("Hello, Bob!")).unwrap());
+    }
+
+    #[test]
+    fn test_domain_display_fields() {
+        let domain = Value::Struct(vec![
+            ("name".into(), Value::String("Ether Mail".into())),
+            (
+                "chainId".into(),
+                Value::Atomic(AtomicValue::Number(vec![1])),
+            ),
+        ]);
+        let (name, chain_id) = domain_display_fields(&domain);
+        assert_eq!(name.as_deref(), Some("Ether Mail"));
+        assert_eq!(chain_id, Some(vec![1]));
+    }
+
+    #[test]
+    fn test_message_display_fields() {
+        let typed_data = mail("Hello, Bob!");
+        let fields = message_display_fields(&typed_data).unwrap();
+
+        let contents = fields.iter().find(|(name, _)| name == "contents").unwrap();
+        match &contents.1 {
+            DisplayValue::Text(s) => assert_eq!(s, "Hello, Bob!"),
+            _ => panic!("expected text"),
+        }
+
+        let from = fields.iter().find(|(name, _)| name == "from").unwrap();
+        match &from.1 {
+            DisplayValue::Struct(person_fields) => {
+                let name = person_fields.iter().find(|(n, _)| n == "name").unwrap();
+                match &name.1 {
+                    DisplayValue::Text(s) => assert_eq!(s, "Cow"),
+                    _ => panic!("expected text"),
+                }
+            }
+            _ => panic!("expected struct"),
+        }
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        // Build `uint256[]` nested MAX_DEPTH + 2 levels deep, past what MAX_DEPTH allows.
+        let mut type_name = String::from("uint256");
+        let mut value = Value::Atomic(AtomicValue::Number(vec![1]));
+        for _ in 0..(MAX_DEPTH + 2) {
+            type_name.push_str("[]");
+            value = Value::Array(vec![value]);
+        }
+
+        let mut types = TypeSet::new();
+        types.insert(
+            "Envelope".into(),
+            vec![FieldType {
+                name: "data".into(),
+                type_name: type_name.clone(),
+            }],
+        );
+        let message = Value::Struct(vec![("data".into(), value)]);
+
+        assert_eq!(
+            hash_struct("Envelope", &message, &types),
+            Err(()),
+            "recursion past MAX_DEPTH must be rejected, not overflow the stack"
+        );
+    }
+}
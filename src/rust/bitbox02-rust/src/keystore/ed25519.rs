@@ -36,6 +36,10 @@ pub fn get_xpub(keypath: &[u32]) -> Result<Xpub, ()> {
 pub struct SignResult {
     pub signature: [u8; 64],
     pub public_key: ed25519_dalek::PublicKey,
+    /// Serialized COSE_Sign1 structure, set only by [`sign_data`].
+    pub cose_sign1: Option<Vec<u8>>,
+    /// Serialized COSE_Key for `public_key`, set only by [`sign_data`].
+    pub cose_key: Option<Vec<u8>>,
 }
 
 pub fn sign(keypath: &[u32], msg: &[u8; 32]) -> Result<SignResult, ()> {
@@ -46,6 +50,158 @@ pub fn sign(keypath: &[u32], msg: &[u8; 32]) -> Result<SignResult, ()> {
     Ok(SignResult {
         signature: secret_key.sign(msg, &public_key).to_bytes(),
         public_key,
+        cose_sign1: None,
+        cose_key: None,
+    })
+}
+
+/// Batch-signs `requests`, a list of `(keypath, msg)` pairs, reading the expanded ed25519 root
+/// seed only once. Requests that share the same keypath are deduplicated, since a Cardano
+/// transaction signs the same tx body hash with every keypath it needs a witness for and only one
+/// witness per keypath is needed.
+///
+/// Not yet called from the Cardano transaction-signing flow; switching it over from per-witness
+/// `sign()` calls is tracked as follow-up work outside this module.
+pub fn sign_batch(requests: &[(Vec<u32>, [u8; 32])]) -> Result<Vec<SignResult>, ()> {
+    let root = get_seed()?;
+    let xprv_root = Xprv::from_normalize(
+        &root[..ED25519_EXPANDED_SECRET_KEY_SIZE],
+        &root[ED25519_EXPANDED_SECRET_KEY_SIZE..],
+    );
+
+    let mut seen_keypaths: alloc::collections::BTreeSet<&[u32]> =
+        alloc::collections::BTreeSet::new();
+    let mut results = Vec::new();
+    for (keypath, msg) in requests {
+        if !seen_keypaths.insert(keypath.as_slice()) {
+            continue;
+        }
+        let xprv = xprv_root.derive_path(keypath);
+        let secret_key =
+            ed25519_dalek::ExpandedSecretKey::from_bytes(&xprv.expanded_secret_key()[..])
+                .or(Err(()))?;
+        let public_key = ed25519_dalek::PublicKey::from(&secret_key);
+        results.push(SignResult {
+            signature: secret_key.sign(msg, &public_key).to_bytes(),
+            public_key,
+            cose_sign1: None,
+            cose_key: None,
+        });
+    }
+    Ok(results)
+}
+
+/// Minimal CBOR encoder covering only the major types needed to assemble a COSE_Sign1 structure
+/// and a COSE_Key: unsigned/negative integers, byte/text strings, arrays and maps, all with
+/// definite lengths.
+mod cbor {
+    use alloc::vec::Vec;
+
+    fn head(major: u8, n: u64) -> Vec<u8> {
+        let major = major << 5;
+        let mut out = Vec::new();
+        if n < 24 {
+            out.push(major | (n as u8));
+        } else if n <= u8::MAX as u64 {
+            out.push(major | 24);
+            out.push(n as u8);
+        } else if n <= u16::MAX as u64 {
+            out.push(major | 25);
+            out.extend_from_slice(&(n as u16).to_be_bytes());
+        } else if n <= u32::MAX as u64 {
+            out.push(major | 26);
+            out.extend_from_slice(&(n as u32).to_be_bytes());
+        } else {
+            out.push(major | 27);
+            out.extend_from_slice(&n.to_be_bytes());
+        }
+        out
+    }
+
+    pub fn uint(n: u64) -> Vec<u8> {
+        head(0, n)
+    }
+
+    /// CBOR negative integer, encoding the value `-1 - n`.
+    pub fn neg_int(n: u64) -> Vec<u8> {
+        head(1, n)
+    }
+
+    pub fn bstr(data: &[u8]) -> Vec<u8> {
+        let mut out = head(2, data.len() as u64);
+        out.extend_from_slice(data);
+        out
+    }
+
+    pub fn tstr(s: &str) -> Vec<u8> {
+        let mut out = head(3, s.len() as u64);
+        out.extend_from_slice(s.as_bytes());
+        out
+    }
+
+    pub fn array_head(n: u64) -> Vec<u8> {
+        head(4, n)
+    }
+
+    pub fn map_head(n: u64) -> Vec<u8> {
+        head(5, n)
+    }
+}
+
+/// Signs `payload` for CIP-8/CIP-30 `signData`, producing a COSE_Sign1 whose protected header
+/// carries `alg: EdDSA` and the signing `address_bytes`, and a COSE_Key so the host can verify the
+/// signature against the public key.
+///
+/// See https://github.com/cardano-foundation/CIPs/tree/master/CIP-0008 and
+/// https://github.com/cardano-foundation/CIPs/tree/master/CIP-0030.
+///
+/// Not yet called from a `signData` request handler; wiring it into the Cardano app's request
+/// dispatch is tracked as follow-up work outside this module.
+pub fn sign_data(keypath: &[u32], payload: &[u8], address_bytes: &[u8]) -> Result<SignResult, ()> {
+    let xprv = get_xprv(keypath)?;
+    let secret_key = ed25519_dalek::ExpandedSecretKey::from_bytes(&xprv.expanded_secret_key()[..])
+        .or(Err(()))?;
+    let public_key = ed25519_dalek::PublicKey::from(&secret_key);
+
+    // protected header: { 1: -8 (alg: EdDSA), "address": bstr(address_bytes) }
+    let mut protected = cbor::map_head(2);
+    protected.extend(cbor::uint(1));
+    protected.extend(cbor::neg_int(7));
+    protected.extend(cbor::tstr("address"));
+    protected.extend(cbor::bstr(address_bytes));
+
+    // Sig_structure = ["Signature1", protected, external_aad, payload]
+    let mut sig_structure = cbor::array_head(4);
+    sig_structure.extend(cbor::tstr("Signature1"));
+    sig_structure.extend(cbor::bstr(&protected));
+    sig_structure.extend(cbor::bstr(b""));
+    sig_structure.extend(cbor::bstr(payload));
+
+    // ed25519 hashes internally, so the assembled Sig_structure is signed directly, not a
+    // pre-hash of it.
+    let signature = secret_key.sign(&sig_structure, &public_key).to_bytes();
+
+    // COSE_Sign1 = [protected, unprotected, payload, signature]
+    let mut cose_sign1 = cbor::array_head(4);
+    cose_sign1.extend(cbor::bstr(&protected));
+    cose_sign1.extend(cbor::map_head(0));
+    cose_sign1.extend(cbor::bstr(payload));
+    cose_sign1.extend(cbor::bstr(&signature));
+
+    // COSE_Key = { 1: 1 (kty: OKP), -1: 6 (crv: Ed25519), -2: bstr(x: public key) }
+    let mut cose_key = cbor::map_head(3);
+    cose_key.extend(cbor::uint(1));
+    cose_key.extend(cbor::uint(1));
+    cose_key.extend(cbor::neg_int(0));
+    cose_key.extend(cbor::uint(6));
+    cose_key.extend(cbor::neg_int(1));
+    cose_key.extend(cbor::bstr(public_key.as_bytes()));
+
+    Ok(SignResult {
+        signature,
+        public_key,
+        cose_sign1: Some(cose_sign1),
+        cose_key: Some(cose_key),
     })
 }
 
@@ -106,4 +262,63 @@ mod tests {
         assert_eq!(xpub.pubkey_bytes(), b"\xab\x58\xbd\x94\x7e\x2b\xf6\x64\xa7\xc0\x66\xde\x2e\xf0\x24\x0e\xfc\x24\xf3\x6e\xfd\x50\x2d\xf8\x83\x93\xe1\x96\xaf\x3c\x91\x8e");
         assert_eq!(xpub.chain_code(), b"\xf2\x00\x13\x38\x58\x02\xa6\xf9\xc0\x5e\xe7\xb0\x36\x16\xad\xf6\x9f\x5f\x9e\xc4\x32\x53\xa5\xd0\x8b\xe9\x65\x79\x81\x90\x83\xbb");
     }
+
+    #[test]
+    fn test_sign_batch() {
+        mock_unlocked();
+
+        let msg1 = [1u8; 32];
+        let msg2 = [2u8; 32];
+        let requests = alloc::vec![
+            (alloc::vec::Vec::new(), msg1),
+            // Duplicate (keypath, msg) pair: must collapse into a single witness.
+            (alloc::vec::Vec::new(), msg1),
+            (alloc::vec![10 + HARDENED_OFFSET, 10], msg2),
+        ];
+        let results = sign_batch(&requests).unwrap();
+        assert_eq!(results.len(), 2);
+
+        assert_eq!(results[0].public_key.as_bytes(), b"\x1c\xc2\xc8\x0d\x6f\xb0\x3e\xc0\x9e\x8a\x26\x8b\xaa\x45\xd4\xca\x2a\xfe\x5c\x5a\xc4\xdb\x3e\xe2\x9c\x7a\xd2\x37\x55\xab\xdc\x14");
+        assert_eq!(results[1].public_key.as_bytes(), b"\xab\x58\xbd\x94\x7e\x2b\xf6\x64\xa7\xc0\x66\xde\x2e\xf0\x24\x0e\xfc\x24\xf3\x6e\xfd\x50\x2d\xf8\x83\x93\xe1\x96\xaf\x3c\x91\x8e");
+
+        assert!(results[0]
+            .public_key
+            .verify(
+                &msg1,
+                &ed25519_dalek::Signature::from_bytes(&results[0].signature).unwrap()
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn test_sign_data() {
+        mock_unlocked();
+
+        let payload = b"hello world";
+        let address = b"\x01\x02\x03\x04";
+        let result = sign_data(&[], payload, address).unwrap();
+
+        // Re-derive the exact bytes that were signed and check the signature verifies.
+        let mut protected = cbor::map_head(2);
+        protected.extend(cbor::uint(1));
+        protected.extend(cbor::neg_int(7));
+        protected.extend(cbor::tstr("address"));
+        protected.extend(cbor::bstr(address));
+        let mut sig_structure = cbor::array_head(4);
+        sig_structure.extend(cbor::tstr("Signature1"));
+        sig_structure.extend(cbor::bstr(&protected));
+        sig_structure.extend(cbor::bstr(b""));
+        sig_structure.extend(cbor::bstr(payload));
+
+        let signature = ed25519_dalek::Signature::from_bytes(&result.signature).unwrap();
+        assert!(result.public_key.verify(&sig_structure, &signature).is_ok());
+
+        let cose_sign1 = result.cose_sign1.unwrap();
+        assert_eq!(cose_sign1[0], 0x84); // array(4)
+        assert!(cose_sign1.ends_with(&cbor::bstr(&result.signature)));
+
+        let cose_key = result.cose_key.unwrap();
+        assert_eq!(cose_key[0], 0xa3); // map(3)
+        assert!(cose_key.ends_with(&cbor::bstr(result.public_key.as_bytes())));
+    }
 }